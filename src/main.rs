@@ -1,8 +1,16 @@
 extern crate crossterm;
 
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use crossterm::{cursor, style, terminal, ExecutableCommand, QueueableCommand};
-use std::io::{stdin, stdout, Read, Write};
-use std::sync::mpsc::channel;
+use std::io::{stdout, BufWriter, Write};
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+// the game loop targets this frame time; event::poll() blocks for up to this
+// long waiting for input before giving up and rendering the next frame, so
+// this doubles as our frame rate
+const FRAME_TIME: Duration = Duration::from_millis(33);
 
 // NOTES
 
@@ -14,25 +22,48 @@ use std::sync::mpsc::channel;
 // queueing is useful for stacking terminal writes / commands for better
 // performance
 
+#[cfg(not(feature = "async"))]
 fn main() {
+    // acquire stdout's lock once and wrap it in a BufWriter, so every
+    // queue/write/flush for the rest of the program goes through one
+    // buffered, pre-locked sink instead of re-locking and re-flushing on
+    // every call
+    let stdout = stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+
     // the terminal has to be prepared for effective operation
-    setup_terminal();
+    setup_terminal(&mut writer);
 
-    run_game().ok();
+    run_game(&mut writer).ok();
 
     // the terminal will not automatically return to its initial state after the
     // program exits, so we must make sure we undo each initialization step
     // manually
-    cleanup_terminal();
+    cleanup_terminal(&mut writer);
+}
+
+// with the "async" feature enabled, the example runs on an async runtime
+// instead, driving input through EventStream rather than event::poll()
+#[cfg(feature = "async")]
+#[tokio::main]
+async fn main() {
+    let stdout = stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+
+    setup_terminal(&mut writer);
+
+    run_game_async(&mut writer).await.ok();
+
+    cleanup_terminal(&mut writer);
 }
 
-fn setup_terminal() {
+fn setup_terminal<W: Write>(writer: &mut W) {
     // STEP 1
     // switch to the alternate terminal window
     // the alternate terminal window empties out the terminal's contents and
     // effectively acts as a new terminal until alternate window is disabled,
     // at which point the previous contents will be restored
-    stdout().execute(terminal::EnterAlternateScreen).unwrap();
+    writer.execute(terminal::EnterAlternateScreen).unwrap();
 
     // STEP 2
     // enable the terminal raw mode
@@ -44,21 +75,29 @@ fn setup_terminal() {
 
     // STEP 3
     // disable the cursor
-    stdout().execute(cursor::Hide).unwrap();
+    writer.execute(cursor::Hide).unwrap();
+
+    // STEP 4
+    // start reporting mouse events, so clicks can be used to move the player
+    writer.execute(EnableMouseCapture).unwrap();
 }
 
-fn cleanup_terminal() {
+fn cleanup_terminal<W: Write>(writer: &mut W) {
     // STEP 1
-    // reenable the cursor
-    stdout().execute(cursor::Show).unwrap();
-    
+    // stop reporting mouse events
+    writer.execute(DisableMouseCapture).unwrap();
+
     // STEP 2
+    // reenable the cursor
+    writer.execute(cursor::Show).unwrap();
+
+    // STEP 3
     // disable raw mode
     terminal::disable_raw_mode().unwrap();
 
-    // STEP 3
+    // STEP 4
     // leave the alternate screen
-    stdout().execute(terminal::LeaveAlternateScreen).unwrap();
+    writer.execute(terminal::LeaveAlternateScreen).unwrap();
 }
 
 #[derive(Debug, Clone)]
@@ -68,103 +107,277 @@ impl From<std::io::Error> for GameError {
     fn from(_: std::io::Error) -> Self { GameError {} }
 }
 
-impl From<crossterm::ErrorKind> for GameError {
-    fn from(_: crossterm::ErrorKind) -> Self { GameError{} }
+// holds the state that depends on the terminal's current dimensions, so it
+// only needs to be recomputed when a resize event actually arrives instead
+// of on every frame
+struct World {
+    // the terminal's characters are half as wide as they are tall, so the
+    // world is half as many tiles wide as the terminal has columns
+    width: i32,
+    height: i32,
 }
 
-// run_game avoids .unwrap() calls in order to ensure that control can return
-// to main() before program end so the terminal cleanup code can be called
-fn run_game() -> Result<(), GameError> {
-    // IMMEDIATE KEYBOARD INPUT SETUP
+impl World {
+    fn from_terminal_size(cols: u16, rows: u16) -> Self {
+        World {
+            width: cols as i32 / 2,
+            height: rows as i32,
+        }
+    }
 
-    // STEP 1
-    // create a channel for sending messages between threads
-    let (ctrls_sender, ctrls_receiver) = channel::<char>();
+    // keep a point inside the world's bounds
+    fn clamp(&self, x: &mut i32, y: &mut i32) {
+        if *x < 0 {
+            *x = 0;
+        }
+        if *y < 0 {
+            *y = 0;
+        }
+        if *x >= self.width {
+            *x = self.width - 1;
+        }
+        if *y >= self.height {
+            *y = self.height - 1;
+        }
+    }
+}
 
-    // STEP 2
-    std::thread::spawn(move || {
-        // continously wait for a single character and send it on the channel
-        // this only works because we enabled raw mode
-        loop {
-            let mut buf = [0u8; 1]; // create a buffer for a single byte
-            stdin().read_exact(&mut buf).unwrap(); // read byte into the buffer
-            ctrls_sender.send(buf[0] as char).unwrap(); // send char on channel
+// a single character on screen, along with the color it's drawn in
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    ch: char,
+    fg: style::Color,
+}
+
+impl Cell {
+    const BLANK: Cell = Cell {
+        ch: ' ',
+        fg: style::Color::Reset,
+    };
+
+    // a sentinel that never matches a real cell, used to force every cell in
+    // the front buffer to be considered "changed" after a resize
+    const DIRTY: Cell = Cell {
+        ch: '\0',
+        fg: style::Color::Reset,
+    };
+}
+
+// a double-buffered screen: drawing writes into the back buffer, and
+// present() only emits the terminal commands needed to turn the front buffer
+// (what's actually on screen) into the back buffer (what we just drew), then
+// swaps the two. this turns per-frame output from O(screen) into O(changed
+// cells) instead of redrawing everything every frame.
+struct Screen {
+    width: usize,
+    height: usize,
+    front: Vec<Cell>,
+    back: Vec<Cell>,
+}
+
+impl Screen {
+    fn new(width: usize, height: usize) -> Self {
+        Screen {
+            width,
+            height,
+            front: vec![Cell::DIRTY; width * height],
+            back: vec![Cell::BLANK; width * height],
         }
-    });
+    }
+
+    // reallocate both buffers for the new size and mark the whole front
+    // buffer dirty so the next present() does a full repaint
+    fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.front = vec![Cell::DIRTY; width * height];
+        self.back = vec![Cell::BLANK; width * height];
+    }
+
+    // reset the back buffer to blank ahead of drawing a new frame
+    fn clear(&mut self) {
+        self.back.fill(Cell::BLANK);
+    }
 
+    fn set(&mut self, x: usize, y: usize, ch: char, fg: style::Color) {
+        if x < self.width && y < self.height {
+            self.back[y * self.width + x] = Cell { ch, fg };
+        }
+    }
+
+    // diff the back buffer against the front buffer, emit only the commands
+    // needed to draw the cells that changed, then swap the buffers
+    fn present<W: Write>(&mut self, writer: &mut W) -> Result<(), GameError> {
+        let mut last_fg = None;
+        for i in 0..self.back.len() {
+            if self.back[i] == self.front[i] {
+                continue;
+            }
+            let cell = self.back[i];
+            let x = (i % self.width) as u16;
+            let y = (i / self.width) as u16;
+            writer.queue(cursor::MoveTo(x, y))?;
+            // only emit a color command when the color actually changed, to
+            // coalesce consecutive cells of the same color
+            if last_fg != Some(cell.fg) {
+                writer.queue(style::SetForegroundColor(cell.fg))?;
+                last_fg = Some(cell.fg);
+            }
+            write!(writer, "{}", cell.ch)?;
+        }
+        writer.flush()?;
+        std::mem::swap(&mut self.front, &mut self.back);
+        Ok(())
+    }
+}
+
+// run_game avoids .unwrap() calls in order to ensure that control can return
+// to main() before program end so the terminal cleanup code can be called
+#[cfg(not(feature = "async"))]
+fn run_game<W: Write>(writer: &mut W) -> Result<(), GameError> {
     // SIMPLE EXAMPLE GAME
-    // the terminal's characters are half as wide as they are tall, so the game
-    // renders objects as two characters wide
 
     let mut player_x: i32 = 0;
     let mut player_y: i32 = 0;
 
+    // STEP 1
+    // read the terminal's size once up front; after this it's only updated
+    // when an Event::Resize arrives, rather than every frame
+    let (term_width, term_height) = terminal::size()?;
+    let mut world = World::from_terminal_size(term_width, term_height);
+    let mut screen = Screen::new(term_width as usize, term_height as usize);
+
     loop {
         // GAME CYCLE
 
         // STEP 1
-        // process any controls stored in the channel
-        while let Ok(ctrl) = ctrls_receiver.try_recv() {
-            match ctrl {
-                'w' => player_y -= 1,
-                's' => player_y += 1,
-                'a' => player_x -= 1,
-                'd' => player_x += 1,
-                'q' => return Ok(()),
-                _ => (),
+        // wait up to one frame's worth of time for an input event, then
+        // process it, instead of busy-spinning a dedicated reader thread
+        if event::poll(FRAME_TIME)? {
+            let event = event::read()?;
+            if handle_event(event, &mut world, &mut screen, &mut player_x, &mut player_y).is_break()
+            {
+                return Ok(());
             }
         }
         // keep the player in the terminal
-        let (term_width, term_height) = terminal::size()?;
-        let world_width = term_width as i32 / 2;
-        let world_height = term_height as i32;
-        if player_x < 0 {
-            player_x = 0;
-        }
-        if player_y < 0 {
-            player_y = 0;
-        }
-        if player_x >= world_width {
-            player_x = world_width - 1;
+        world.clamp(&mut player_x, &mut player_y);
+
+        // STEP 2
+        // draw and present this frame
+        draw_frame(&mut screen, writer, player_x, player_y)?;
+    }
+}
+
+// applies a single input event to the game state; shared by the synchronous
+// and async game loops. returns ControlFlow::Break when the event means the
+// game should exit (pressing q or Ctrl-C).
+fn handle_event(
+    event: Event,
+    world: &mut World,
+    screen: &mut Screen,
+    player_x: &mut i32,
+    player_y: &mut i32,
+) -> ControlFlow<()> {
+    match event {
+        // most Unix terminals only ever report KeyEventKind::Press, but
+        // Windows' ConPTY also reports KeyEventKind::Release for the same
+        // keystroke; ignore it so a single tap doesn't move the player twice
+        Event::Key(key_event) if key_event.kind != KeyEventKind::Release => match key_event.code {
+            KeyCode::Char('w') | KeyCode::Up => *player_y -= 1,
+            KeyCode::Char('s') | KeyCode::Down => *player_y += 1,
+            KeyCode::Char('a') | KeyCode::Left => *player_x -= 1,
+            KeyCode::Char('d') | KeyCode::Right => *player_x += 1,
+            KeyCode::Char('q') => return ControlFlow::Break(()),
+            KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                return ControlFlow::Break(())
+            }
+            _ => (),
+        },
+        Event::Resize(cols, rows) => {
+            *world = World::from_terminal_size(cols, rows);
+            screen.resize(cols as usize, rows as usize);
         }
-        if player_y >= world_height {
-            player_y = world_height - 1;
+        Event::Mouse(mouse_event) => {
+            if let MouseEventKind::Down(MouseButton::Left) = mouse_event.kind {
+                // invert the 2-cells-per-tile horizontal scaling to go from
+                // a terminal column back to a world tile
+                *player_x = mouse_event.column as i32 / 2;
+                *player_y = mouse_event.row as i32;
+            }
         }
+        _ => (),
+    }
+    ControlFlow::Continue(())
+}
 
-        // STEP 2
-        // clear the terminal
-        // it's okay to do this because we're working in the alternate terminal,
-        // the original terminal contents will be unaffected
-        stdout()
-            .queue(terminal::Clear(terminal::ClearType::All))?;
-
-        // STEP 3
-        // write some instructions in the top left :)
-        // nobody should do this irl, writing the same thing every frame is
-        // very inefficient
-        stdout()
-            .queue(style::SetForegroundColor(style::Color::White))?
-            .queue(cursor::MoveTo(0, 0))?
-            .write("move with wasd, press q to exit".as_bytes())?;
-
-        // STEP 4
-        // do whatever rendering needs to be done
-        // in this case we move the cursor to the position indicated by player
-        // x and y, set a color, and write two characters
-        // .queue()? returns the the calling object, so we can chain calls
-        // until .write()
-        stdout()
-            .queue(cursor::MoveTo(player_x as u16 * 2, player_y as u16))?
-            .queue(style::SetForegroundColor(style::Color::Rgb {
-                r: 255,
-                g: 0,
-                b: 0,
-            }))?
-            .write("[]".as_bytes())?;
-
-        // STEP 5
-        // since the last commands and writes were queued instead of executed,
-        // we have to manually flush the output buffer
-        stdout().flush()?;
+// draws the instructions and the player into the screen's back buffer and
+// presents it; shared by the synchronous and async game loops
+fn draw_frame<W: Write>(
+    screen: &mut Screen,
+    writer: &mut W,
+    player_x: i32,
+    player_y: i32,
+) -> Result<(), GameError> {
+    screen.clear();
+
+    // write some instructions in the top left :)
+    for (i, ch) in "move with wasd/arrows or click, press q to exit"
+        .chars()
+        .enumerate()
+    {
+        screen.set(i, 0, ch, style::Color::White);
+    }
+
+    // draw the player at its position, two characters wide
+    let player_color = style::Color::Rgb { r: 255, g: 0, b: 0 };
+    screen.set(player_x as usize * 2, player_y as usize, '[', player_color);
+    screen.set(player_x as usize * 2 + 1, player_y as usize, ']', player_color);
+
+    // diff the back buffer against what's already on screen and only emit
+    // the commands needed to draw what changed
+    screen.present(writer)
+}
+
+// async variant of run_game, built on crossterm's EventStream instead of
+// event::poll()/event::read(). input is consumed as a stream alongside a
+// fixed-rate interval timer, via futures::select!, so keypresses are handled
+// immediately while rendering stays on a steady cadence, without a dedicated
+// reader thread or busy polling.
+#[cfg(feature = "async")]
+async fn run_game_async<W: Write + Unpin>(writer: &mut W) -> Result<(), GameError> {
+    use futures::{FutureExt, StreamExt};
+
+    let mut player_x: i32 = 0;
+    let mut player_y: i32 = 0;
+
+    let (term_width, term_height) = terminal::size()?;
+    let mut world = World::from_terminal_size(term_width, term_height);
+    let mut screen = Screen::new(term_width as usize, term_height as usize);
+
+    let mut events = event::EventStream::new();
+    let mut ticker = tokio::time::interval(FRAME_TIME);
+
+    loop {
+        futures::select! {
+            event = events.next().fuse() => {
+                match event {
+                    Some(Ok(event)) => {
+                        let flow =
+                            handle_event(event, &mut world, &mut screen, &mut player_x, &mut player_y);
+                        if flow.is_break() {
+                            return Ok(());
+                        }
+                    }
+                    Some(Err(err)) => return Err(err.into()),
+                    // the event stream ended, e.g. stdin was closed
+                    None => return Ok(()),
+                }
+            }
+            _ = ticker.tick().fuse() => {
+                world.clamp(&mut player_x, &mut player_y);
+                draw_frame(&mut screen, writer, player_x, player_y)?;
+            }
+        }
     }
 }
\ No newline at end of file